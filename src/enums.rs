@@ -7,8 +7,10 @@
 pub use self::Vg_ClientRequest::*;
 pub use self::Vg_MemCheckClientRequest::*;
 pub use self::Vg_CallgrindClientRequest::*;
+pub use self::Vg_CachegrindClientRequest::*;
 pub use self::Vg_TCheckClientRequest::*;
 pub use self::Vg_DRDClientRequest::*;
+pub use self::Vg_DHATClientRequest::*;
 
 macro_rules! VG_USERREQ_TOOL_BASE ( ($a:expr, $b:expr) => (
     ((($a as isize) & 0xff) << 24)
@@ -24,6 +26,7 @@ pub enum Vg_ClientRequest {
     VG_USERREQ__CLIENT_CALL3 = 0x1104,
     VG_USERREQ__COUNT_ERRORS = 0x1201,
     VG_USERREQ__GDB_MONITOR_COMMAND = 0x1202,
+    VG_USERREQ__CLO_CHANGE = 0x1203,
     VG_USERREQ__MALLOCLIKE_BLOCK = 0x1301,
     VG_USERREQ__RESIZEINPLACE_BLOCK = 0x130b,
     VG_USERREQ__FREELIKE_BLOCK   = 0x1302,
@@ -62,6 +65,8 @@ pub enum Vg_MemCheckClientRequest {
     VG_USERREQ__CREATE_BLOCK,
     VG_USERREQ__MAKE_MEM_DEFINED_IF_ADDRESSABLE,
     VG_USERREQ__COUNT_LEAK_BLOCKS,
+    VG_USERREQ__ENABLE_ADDR_ERROR_REPORTING_IN_RANGE,
+    VG_USERREQ__DISABLE_ADDR_ERROR_REPORTING_IN_RANGE,
 }
 
 #[repr(C)]
@@ -74,10 +79,62 @@ pub enum Vg_CallgrindClientRequest {
     VG_USERREQ__STOP_INSTRUMENTATION,
 }
 
+#[repr(C)]
+pub enum Vg_CachegrindClientRequest {
+    VG_USERREQ__CG_START_INSTRUMENTATION = VG_USERREQ_TOOL_BASE!('C','G'),
+    VG_USERREQ__CG_STOP_INSTRUMENTATION,
+}
+
 #[repr(C)]
 pub enum Vg_TCheckClientRequest {
     VG_USERREQ__HG_CLEAN_MEMORY = VG_USERREQ_TOOL_BASE!('H','G'),
-    _Vg_TCheckClientRequest_dummy, // suppress error about univariant enum repr
+
+    // The rest are nominally for Helgrind's internal use, but the
+    // annotation macros in `helgrind.h` are built on them.
+    _VG_USERREQ__HG_SET_MY_PTHREAD_T = VG_USERREQ_TOOL_BASE!('H','G') + 256,
+    _VG_USERREQ__HG_PTH_API_ERROR,
+    _VG_USERREQ__HG_PTHREAD_JOIN_POST,
+    _VG_USERREQ__HG_PTHREAD_MUTEX_INIT_POST,
+    _VG_USERREQ__HG_PTHREAD_MUTEX_DESTROY_PRE,
+    _VG_USERREQ__HG_PTHREAD_MUTEX_UNLOCK_PRE,
+    _VG_USERREQ__HG_PTHREAD_MUTEX_UNLOCK_POST,
+    _VG_USERREQ__HG_PTHREAD_MUTEX_ACQUIRE_PRE,
+    _VG_USERREQ__HG_PTHREAD_MUTEX_ACQUIRE_POST,
+    _VG_USERREQ__HG_PTHREAD_COND_SIGNAL_PRE,
+    _VG_USERREQ__HG_PTHREAD_COND_BROADCAST_PRE,
+    _VG_USERREQ__HG_PTHREAD_COND_WAIT_PRE,
+    _VG_USERREQ__HG_PTHREAD_COND_WAIT_POST,
+    _VG_USERREQ__HG_PTHREAD_COND_DESTROY_PRE,
+    _VG_USERREQ__HG_PTHREAD_RWLOCK_INIT_POST,
+    _VG_USERREQ__HG_PTHREAD_RWLOCK_DESTROY_PRE,
+    _VG_USERREQ__HG_PTHREAD_RWLOCK_LOCK_PRE,
+    _VG_USERREQ__HG_PTHREAD_RWLOCK_ACQUIRED,
+    _VG_USERREQ__HG_PTHREAD_RWLOCK_RELEASED,
+    _VG_USERREQ__HG_PTHREAD_RWLOCK_UNLOCK_POST,
+    _VG_USERREQ__HG_POSIX_SEM_INIT_POST,
+    _VG_USERREQ__HG_POSIX_SEM_DESTROY_PRE,
+    _VG_USERREQ__HG_POSIX_SEM_RELEASED,
+    _VG_USERREQ__HG_POSIX_SEM_ACQUIRED,
+    _VG_USERREQ__HG_PTHREAD_BARRIER_INIT_PRE,
+    _VG_USERREQ__HG_PTHREAD_BARRIER_WAIT_PRE,
+    _VG_USERREQ__HG_PTHREAD_BARRIER_DESTROY_PRE,
+    _VG_USERREQ__HG_PTHREAD_SPIN_INIT_OR_UNLOCK_PRE,
+    _VG_USERREQ__HG_PTHREAD_SPIN_INIT_OR_UNLOCK_POST,
+    _VG_USERREQ__HG_PTHREAD_SPIN_LOCK_PRE,
+    _VG_USERREQ__HG_PTHREAD_SPIN_LOCK_POST,
+    _VG_USERREQ__HG_PTHREAD_SPIN_DESTROY_PRE,
+    _VG_USERREQ__HG_CLIENTREQ_UNIMP,
+    _VG_USERREQ__HG_USERSO_SEND_PRE,
+    _VG_USERREQ__HG_USERSO_RECV_POST,
+    _VG_USERREQ__HG_USERSO_FORGET_ALL,
+    _VG_USERREQ__HG_RESERVED2,
+    _VG_USERREQ__HG_RESERVED3,
+    _VG_USERREQ__HG_RESERVED4,
+    _VG_USERREQ__HG_ARANGE_MAKE_UNTRACKED,
+    _VG_USERREQ__HG_ARANGE_MAKE_TRACKED,
+    _VG_USERREQ__HG_PTHREAD_BARRIER_RESIZE_PRE,
+    _VG_USERREQ__HG_CLEAN_MEMORY_HEAPBLOCK,
+    _VG_USERREQ__HG_PTHREAD_COND_INIT_POST,
 }
 
 #[repr(C)]
@@ -94,4 +151,30 @@ pub enum Vg_DRDClientRequest {
    VG_USERREQ__DRD_RECORD_LOADS,
    VG_USERREQ__DRD_RECORD_STORES,
    VG_USERREQ__DRD_SET_THREAD_NAME,
+   VG_USERREQ__DRD_ANNOTATION_UNIMP,
+   VG_USERREQ__DRD_ANNOTATE_BARRIER_INIT_PRE,
+   VG_USERREQ__DRD_ANNOTATE_BARRIER_DESTROY,
+   VG_USERREQ__DRD_ANNOTATE_BARRIER_WAIT_PRE,
+   VG_USERREQ__DRD_ANNOTATE_BARRIER_WAIT_POST,
+   VG_USERREQ__DRD_ANNOTATE_SEM_INIT_PRE,
+   VG_USERREQ__DRD_ANNOTATE_SEM_DESTROY_POST,
+   VG_USERREQ__DRD_ANNOTATE_SEM_WAIT_PRE,
+   VG_USERREQ__DRD_ANNOTATE_SEM_WAIT_POST,
+   VG_USERREQ__DRD_ANNOTATE_SEM_POST_PRE,
+   VG_USERREQ__DRD_IGNORE_MUTEX_ORDERING,
+
+   // Binary compatible with the similar Helgrind requests above
+   VG_USERREQ__DRD_ANNOTATE_RWLOCK_CREATE = VG_USERREQ_TOOL_BASE!('H','G') + 256 + 14,
+   VG_USERREQ__DRD_ANNOTATE_RWLOCK_DESTROY = VG_USERREQ_TOOL_BASE!('H','G') + 256 + 15,
+   VG_USERREQ__DRD_ANNOTATE_RWLOCK_ACQUIRED = VG_USERREQ_TOOL_BASE!('H','G') + 256 + 17,
+   VG_USERREQ__DRD_ANNOTATE_RWLOCK_RELEASED = VG_USERREQ_TOOL_BASE!('H','G') + 256 + 18,
+   VG_USERREQ__HELGRIND_ANNOTATION_UNIMP = VG_USERREQ_TOOL_BASE!('H','G') + 256 + 32,
+   VG_USERREQ__DRD_ANNOTATE_HAPPENS_BEFORE = VG_USERREQ_TOOL_BASE!('H','G') + 256 + 33,
+   VG_USERREQ__DRD_ANNOTATE_HAPPENS_AFTER = VG_USERREQ_TOOL_BASE!('H','G') + 256 + 34,
+}
+
+#[repr(C)]
+pub enum Vg_DHATClientRequest {
+    VG_USERREQ__DHAT_AD_HOC_EVENT = VG_USERREQ_TOOL_BASE!('D','H'),
+    VG_USERREQ__DHAT_HISTOGRAM_MEMORY,
 }
@@ -19,6 +19,10 @@
 //! extension of Rust's memory safety guarantee, all of these
 //! functions are marked `unsafe`.  Even so, some of them are
 //! clearly safe, but are still marked `unsafe` for consistency.
+//! The exceptions are `check_slice_defined`, `check_str_defined`,
+//! `make_slice_undefined` and `make_spare_capacity_undefined` in
+//! `memcheck`, which only check or poison memory the caller
+//! already owns.
 //!
 //! When not running under Valgrind, these requests do nothing
 //! and return a default value (usually zero).
@@ -81,6 +85,10 @@ impl FromUsize for *const () {
     fn from_usize(x: usize) -> *const () { x as *const () }
 }
 
+impl FromUsize for bool {
+    fn from_usize(x: usize) -> bool { x != 0 }
+}
+
 impl FromUsize for c_uint {
     fn from_usize(x: usize) -> c_uint { x as c_uint }
 }
@@ -178,7 +186,11 @@ pub mod valgrind {
     //!
     //! [section 3.1]: http://valgrind.org/docs/manual/manual-core-adv.html#manual-core-adv.clientreq
 
+    use libc::c_uint;
+    use std::{fmt, str};
+    use std::error::Error;
     use std::ffi::CString;
+    use std::marker::PhantomData;
     use super::{arch, enums};
 
     wrap!(VG_USERREQ__RUNNING_ON_VALGRIND
@@ -190,8 +202,352 @@ pub mod valgrind {
     wrap!(VG_USERREQ__DISCARD_TRANSLATIONS
         => fn discard_translations(addr: *const (), len: usize) -> ());
 
-    wrap_str!(VG_USERREQ__GDB_MONITOR_COMMAND
-        => fn monitor_command(cmd: &str) -> ());
+    /// Error returned by `monitor_command` when Valgrind does not
+    /// recognise the command.
+    #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+    pub struct UnknownCommand;
+
+    impl Error for UnknownCommand { }
+
+    impl fmt::Display for UnknownCommand {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("unknown monitor command")
+        }
+    }
+
+    /// Execute a gdbserver monitor command, such as
+    /// `"v.info n_errs_found"`.
+    ///
+    /// The command's output goes to the connected debugger, or to
+    /// the Valgrind log if there is none.  Valgrind provides no way
+    /// to return it to the client.
+    #[inline(always)]
+    pub unsafe fn monitor_command(cmd: &str) -> Result<(), UnknownCommand> {
+        let c_str = CString::new(cmd.as_bytes()).unwrap();
+        match arch::request(0, enums::VG_USERREQ__GDB_MONITOR_COMMAND as usize,
+                c_str.as_bytes_with_nul().as_ptr() as usize, 0, 0, 0, 0) {
+            0 => Ok(()),
+            _ => Err(UnknownCommand),
+        }
+    }
+
+    wrap_str!(VG_USERREQ__CLO_CHANGE
+        => fn change_option(opt: &str) -> ());
+
+    /// A command-line option which can be changed while the program
+    /// is running.  `valgrind --help-dyn-options` lists them all;
+    /// use `change_option` for those not covered here.
+    #[derive(Copy, Clone, Debug)]
+    pub enum DynOption<'a> {
+        Verbose,
+        Quiet,
+        Stats(bool),
+        VgdbError(usize),
+        TraceChildren(bool),
+        ChildSilentAfterFork(bool),
+        ShowBelowMain(bool),
+        TimeStamp(bool),
+        /// `"no"`, `"yes"` or `"all"`.
+        GenSuppressions(&'a str),
+        /// Memcheck only.
+        ErrorsForLeakKinds(&'a str),
+        /// Memcheck only.
+        ShowLeakKinds(&'a str),
+        /// Callgrind only.
+        ToggleCollect(&'a str),
+    }
+
+    impl<'a> fmt::Display for DynOption<'a> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            fn yes_no(b: bool) -> &'static str {
+                if b { "yes" } else { "no" }
+            }
+
+            match *self {
+                DynOption::Verbose => write!(f, "--verbose"),
+                DynOption::Quiet => write!(f, "--quiet"),
+                DynOption::Stats(b) => write!(f, "--stats={}", yes_no(b)),
+                DynOption::VgdbError(n) => write!(f, "--vgdb-error={}", n),
+                DynOption::TraceChildren(b) => write!(f, "--trace-children={}", yes_no(b)),
+                DynOption::ChildSilentAfterFork(b)
+                    => write!(f, "--child-silent-after-fork={}", yes_no(b)),
+                DynOption::ShowBelowMain(b) => write!(f, "--show-below-main={}", yes_no(b)),
+                DynOption::TimeStamp(b) => write!(f, "--time-stamp={}", yes_no(b)),
+                DynOption::GenSuppressions(s) => write!(f, "--gen-suppressions={}", s),
+                DynOption::ErrorsForLeakKinds(s) => write!(f, "--errors-for-leak-kinds={}", s),
+                DynOption::ShowLeakKinds(s) => write!(f, "--show-leak-kinds={}", s),
+                DynOption::ToggleCollect(s) => write!(f, "--toggle-collect={}", s),
+            }
+        }
+    }
+
+    /// Change a dynamic option.  Valgrind ignores the request,
+    /// with a warning, if the running tool does not know the option.
+    #[inline(always)]
+    pub unsafe fn change_dyn_option(opt: DynOption) {
+        change_option(&opt.to_string())
+    }
+
+    wrap!(VG_USERREQ__STACK_REGISTER
+        => fn stack_register(start: *const (), end: *const ()) -> c_uint);
+
+    wrap!(VG_USERREQ__STACK_DEREGISTER
+        => fn stack_deregister(id: c_uint) -> ());
+
+    wrap!(VG_USERREQ__STACK_CHANGE
+        => fn stack_change(id: c_uint, start: *const (), end: *const ()) -> ());
+
+    /// A region of memory registered with Valgrind as a stack.
+    ///
+    /// The range is `[start, end)`; note that the underlying
+    /// requests take the address of the highest byte instead.
+    /// The stack is deregistered when this value is dropped.
+    pub struct StackRegistration {
+        id: c_uint,
+    }
+
+    impl StackRegistration {
+        #[inline(always)]
+        pub unsafe fn new(start: *const (), end: *const ()) -> StackRegistration {
+            StackRegistration {
+                id: stack_register(start, (end as usize - 1) as *const ()),
+            }
+        }
+
+        /// The stack ID assigned by Valgrind.
+        #[inline(always)]
+        pub fn id(&self) -> c_uint {
+            self.id
+        }
+
+        /// Move or resize the registered stack to `[start, end)`.
+        #[inline(always)]
+        pub unsafe fn change(&mut self, start: *const (), end: *const ()) {
+            stack_change(self.id, start, (end as usize - 1) as *const ());
+        }
+    }
+
+    impl Drop for StackRegistration {
+        fn drop(&mut self) {
+            unsafe {
+                stack_deregister(self.id);
+            }
+        }
+    }
+
+    // A function run by a `CLIENT_CALL` request receives the Valgrind
+    // thread ID as its first argument.  When not running under Valgrind
+    // we call it directly and pass 0, which is never a valid thread ID.
+
+    /// Run `f` on the real CPU, outside of Valgrind's instrumentation.
+    #[inline(always)]
+    pub unsafe fn non_simd_call0(f: extern "C" fn(usize) -> usize) -> usize {
+        if running_on_valgrind() == 0 {
+            return f(0);
+        }
+        arch::request(0, enums::VG_USERREQ__CLIENT_CALL0 as usize, f as usize, 0, 0, 0, 0)
+    }
+
+    #[inline(always)]
+    pub unsafe fn non_simd_call1(f: extern "C" fn(usize, usize) -> usize,
+                                 a1: usize) -> usize {
+        if running_on_valgrind() == 0 {
+            return f(0, a1);
+        }
+        arch::request(0, enums::VG_USERREQ__CLIENT_CALL1 as usize, f as usize, a1, 0, 0, 0)
+    }
+
+    #[inline(always)]
+    pub unsafe fn non_simd_call2(f: extern "C" fn(usize, usize, usize) -> usize,
+                                 a1: usize, a2: usize) -> usize {
+        if running_on_valgrind() == 0 {
+            return f(0, a1, a2);
+        }
+        arch::request(0, enums::VG_USERREQ__CLIENT_CALL2 as usize, f as usize, a1, a2, 0, 0)
+    }
+
+    #[inline(always)]
+    pub unsafe fn non_simd_call3(f: extern "C" fn(usize, usize, usize, usize) -> usize,
+                                 a1: usize, a2: usize, a3: usize) -> usize {
+        if running_on_valgrind() == 0 {
+            return f(0, a1, a2, a3);
+        }
+        arch::request(0, enums::VG_USERREQ__CLIENT_CALL3 as usize, f as usize, a1, a2, a3, 0)
+    }
+
+    /// Run the closure `f` on the real CPU, outside of Valgrind's
+    /// instrumentation.  It is passed the Valgrind thread ID, or 0
+    /// when not running under Valgrind.
+    ///
+    /// `f` is called through an `extern "C"` trampoline and must
+    /// not panic.
+    #[inline(always)]
+    pub unsafe fn non_simd_call<F>(f: F) -> usize
+        where F: FnOnce(usize) -> usize
+    {
+        extern "C" fn trampoline<F>(tid: usize, data: usize) -> usize
+            where F: FnOnce(usize) -> usize
+        {
+            let f = unsafe { (*(data as *mut Option<F>)).take().unwrap() };
+            f(tid)
+        }
+
+        let mut f = Some(f);
+        non_simd_call1(trampoline::<F>, &mut f as *mut Option<F> as usize)
+    }
+
+    /// Stop reporting errors in the calling thread.  Calls nest,
+    /// and each must be undone by `enable_error_reporting`.
+    #[inline(always)]
+    pub unsafe fn disable_error_reporting() {
+        arch::request(0, enums::VG_USERREQ__CHANGE_ERR_DISABLEMENT as usize, 1, 0, 0, 0, 0);
+    }
+
+    #[inline(always)]
+    pub unsafe fn enable_error_reporting() {
+        arch::request(0, enums::VG_USERREQ__CHANGE_ERR_DISABLEMENT as usize, !0, 0, 0, 0, 0);
+    }
+
+    /// Error reporting is disabled for the current thread while
+    /// this guard is alive.  Guards may be nested.
+    #[must_use]
+    pub struct ErrorsDisabled {
+        // The disablement count is per-thread.
+        _marker: PhantomData<*const ()>,
+    }
+
+    impl ErrorsDisabled {
+        #[inline(always)]
+        pub unsafe fn new() -> ErrorsDisabled {
+            disable_error_reporting();
+            ErrorsDisabled { _marker: PhantomData }
+        }
+    }
+
+    impl Drop for ErrorsDisabled {
+        fn drop(&mut self) {
+            unsafe {
+                enable_error_reporting();
+            }
+        }
+    }
+
+    /// Source location of a code address, as returned by
+    /// `map_ip_to_srcloc`.
+    #[derive(Copy, Clone)]
+    pub struct SrcLoc {
+        buf: [u8; 64],
+        file_len: usize,
+        line: u32,
+    }
+
+    impl SrcLoc {
+        /// The source file name.  Valgrind truncates this to
+        /// 49 bytes, and a character split by the truncation is
+        /// dropped.
+        #[inline(always)]
+        pub fn file(&self) -> &str {
+            str::from_utf8(&self.buf[..self.file_len]).unwrap()
+        }
+
+        #[inline(always)]
+        pub fn line(&self) -> u32 {
+            self.line
+        }
+    }
+
+    impl fmt::Display for SrcLoc {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}:{}", self.file(), self.line)
+        }
+    }
+
+    /// Look up the source file and line of the code address `ip`
+    /// with Valgrind's debuginfo reader.  The request does not
+    /// report the enclosing function.
+    ///
+    /// Returns `None` if the address has no line information.
+    #[inline(always)]
+    pub unsafe fn map_ip_to_srcloc(ip: *const ()) -> Option<SrcLoc> {
+        // Valgrind fills the buffer with "file:line", or leaves it
+        // empty if the lookup fails.
+        let mut buf = [0u8; 64];
+        arch::request(0, enums::VG_USERREQ__MAP_IP_TO_SRCLOC as usize,
+            ip as usize, buf.as_mut_ptr() as usize, 0, 0, 0);
+
+        let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+        let colon = match buf[..len].iter().rposition(|&b| b == b':') {
+            Some(i) => i,
+            None => return None,
+        };
+        let line = match str::from_utf8(&buf[colon+1..len]).ok().and_then(|l| l.parse().ok()) {
+            Some(l) => l,
+            None => return None,
+        };
+        // The truncation can split a multi-byte character; keep the
+        // part that is valid.
+        let file_len = match str::from_utf8(&buf[..colon]) {
+            Ok(_) => colon,
+            Err(e) => e.valid_up_to(),
+        };
+
+        Some(SrcLoc {
+            buf: buf,
+            file_len: file_len,
+            line: line,
+        })
+    }
+
+    // Valgrind's printf requests take a C format string and a pointer
+    // to a `va_list`.  We format on the Rust side and escape every
+    // `%`, so the `va_list` is copied but never read; it only needs
+    // to point at enough memory.
+    #[inline(always)]
+    unsafe fn printf(nr: usize, args: fmt::Arguments) -> usize {
+        if running_on_valgrind() == 0 {
+            return 0;
+        }
+        let msg = fmt::format(args).replace("%", "%%").replace("\0", "");
+        let c_str = CString::new(msg).unwrap();
+        let va_list = [0usize; 4];
+        arch::request(0, nr, c_str.as_ptr() as usize, va_list.as_ptr() as usize, 0, 0, 0)
+    }
+
+    /// Write formatted text to the Valgrind log.  Returns the
+    /// number of characters written.
+    ///
+    /// Usually called through `vg_println!`.
+    #[inline(always)]
+    pub unsafe fn print(args: fmt::Arguments) -> usize {
+        printf(enums::VG_USERREQ__PRINTF_VALIST_BY_REF as usize, args)
+    }
+
+    /// Like `print`, followed by a backtrace of the calling thread.
+    ///
+    /// Usually called through `vg_println_backtrace!`.
+    #[inline(always)]
+    pub unsafe fn print_backtrace(args: fmt::Arguments) -> usize {
+        printf(enums::VG_USERREQ__PRINTF_BACKTRACE_VALIST_BY_REF as usize, args)
+    }
+
+    /// Write a line to the Valgrind log, using `format!` syntax.
+    #[macro_export]
+    macro_rules! vg_println {
+        () => ($crate::valgrind::print(format_args!("\n")));
+        ($fmt:expr) => ($crate::valgrind::print(format_args!(concat!($fmt, "\n"))));
+        ($fmt:expr, $($arg:tt)*) => (
+            $crate::valgrind::print(format_args!(concat!($fmt, "\n"), $($arg)*)));
+    }
+
+    /// Write a line to the Valgrind log, using `format!` syntax,
+    /// followed by a backtrace of the calling thread.
+    #[macro_export]
+    macro_rules! vg_println_backtrace {
+        () => ($crate::valgrind::print_backtrace(format_args!("\n")));
+        ($fmt:expr) => ($crate::valgrind::print_backtrace(format_args!(concat!($fmt, "\n"))));
+        ($fmt:expr, $($arg:tt)*) => (
+            $crate::valgrind::print_backtrace(format_args!(concat!($fmt, "\n"), $($arg)*)));
+    }
 }
 
 pub mod memcheck {
@@ -203,6 +559,12 @@ pub mod memcheck {
     //!
     //! [section 4.7]: http://valgrind.org/docs/manual/mc-manual.html#mc-manual.clientreqs
 
+    use std::{fmt, mem};
+    use std::error::Error;
+    use std::ffi::CString;
+    use std::mem::MaybeUninit;
+    use super::{arch, enums};
+
     wrap!(VG_USERREQ__MALLOCLIKE_BLOCK
         => fn malloclike_block(addr: *const (), size: usize, redzone: usize, is_zeroed: bool) -> ());
 
@@ -212,6 +574,118 @@ pub mod memcheck {
     wrap!(VG_USERREQ__FREELIKE_BLOCK
         => fn freelike_block(addr: *const (), redzone: usize) -> ());
 
+    wrap!(VG_USERREQ__CREATE_MEMPOOL
+        => fn create_mempool(pool: *const (), redzone: usize, is_zeroed: bool) -> ());
+
+    wrap!(VG_USERREQ__CREATE_MEMPOOL
+        => fn create_mempool_ext(pool: *const (), redzone: usize, is_zeroed: bool, flags: usize) -> ());
+
+    wrap!(VG_USERREQ__DESTROY_MEMPOOL
+        => fn destroy_mempool(pool: *const ()) -> ());
+
+    wrap!(VG_USERREQ__MEMPOOL_ALLOC
+        => fn mempool_alloc(pool: *const (), addr: *const (), size: usize) -> ());
+
+    wrap!(VG_USERREQ__MEMPOOL_FREE
+        => fn mempool_free(pool: *const (), addr: *const ()) -> ());
+
+    wrap!(VG_USERREQ__MEMPOOL_TRIM
+        => fn mempool_trim(pool: *const (), addr: *const (), size: usize) -> ());
+
+    wrap!(VG_USERREQ__MOVE_MEMPOOL
+        => fn move_mempool(pool_a: *const (), pool_b: *const ()) -> ());
+
+    wrap!(VG_USERREQ__MEMPOOL_CHANGE
+        => fn mempool_change(pool: *const (), addr_a: *const (), addr_b: *const (), size: usize) -> ());
+
+    wrap!(VG_USERREQ__MEMPOOL_EXISTS
+        => fn mempool_exists(pool: *const ()) -> bool);
+
+    /// Flag for `Mempool::with_flags`: freeing one of the pool's
+    /// superblocks also frees the `malloclike_block` blocks inside
+    /// it.  Only valid together with `MEMPOOL_METAPOOL`.
+    pub const MEMPOOL_AUTO_FREE: usize = 1;
+
+    /// Flag for `Mempool::with_flags`: the pool's chunks are
+    /// superblocks, which the program then splits into blocks
+    /// described with `malloclike_block`.
+    pub const MEMPOOL_METAPOOL: usize = 2;
+
+    /// A memory pool registered with Memcheck.
+    ///
+    /// The pool is identified by its anchor address, usually
+    /// the address of the allocator's own bookkeeping.  It is
+    /// destroyed when this handle is dropped.
+    pub struct Mempool {
+        pool: *const (),
+    }
+
+    impl Mempool {
+        #[inline(always)]
+        pub unsafe fn new(pool: *const (), redzone: usize, is_zeroed: bool) -> Mempool {
+            create_mempool(pool, redzone, is_zeroed);
+            Mempool { pool: pool }
+        }
+
+        /// Create a pool with `MEMPOOL_AUTO_FREE` and/or
+        /// `MEMPOOL_METAPOOL` set.
+        #[inline(always)]
+        pub unsafe fn with_flags(pool: *const (), redzone: usize, is_zeroed: bool,
+                                 flags: usize) -> Mempool {
+            create_mempool_ext(pool, redzone, is_zeroed, flags);
+            Mempool { pool: pool }
+        }
+
+        /// The anchor address identifying this pool.
+        #[inline(always)]
+        pub fn pool(&self) -> *const () {
+            self.pool
+        }
+
+        #[inline(always)]
+        pub unsafe fn alloc(&self, addr: *const (), size: usize) {
+            mempool_alloc(self.pool, addr, size)
+        }
+
+        #[inline(always)]
+        pub unsafe fn free(&self, addr: *const ()) {
+            mempool_free(self.pool, addr)
+        }
+
+        /// Free every chunk outside `[addr, addr+size)`.
+        #[inline(always)]
+        pub unsafe fn trim(&self, addr: *const (), size: usize) {
+            mempool_trim(self.pool, addr, size)
+        }
+
+        /// Tell Memcheck that the pool's anchor has moved.
+        #[inline(always)]
+        pub unsafe fn move_to(&mut self, new_pool: *const ()) {
+            move_mempool(self.pool, new_pool);
+            self.pool = new_pool;
+        }
+
+        /// Tell Memcheck that the chunk at `old_addr` has been
+        /// moved to `new_addr` and now has size `size`.
+        #[inline(always)]
+        pub unsafe fn change(&self, old_addr: *const (), new_addr: *const (), size: usize) {
+            mempool_change(self.pool, old_addr, new_addr, size)
+        }
+
+        #[inline(always)]
+        pub unsafe fn exists(&self) -> bool {
+            mempool_exists(self.pool)
+        }
+    }
+
+    impl Drop for Mempool {
+        fn drop(&mut self) {
+            unsafe {
+                destroy_mempool(self.pool);
+            }
+        }
+    }
+
     wrap!(VG_USERREQ__MAKE_MEM_NOACCESS
         => fn make_mem_noaccess(addr: *const (), len: usize) -> ());
 
@@ -248,6 +722,270 @@ pub mod memcheck {
     generic!(check_mem_is_defined
         => fn check_is_defined<T>(obj: *const T) -> Option<*const ()>);
 
+    /// The first undefined byte found by `check_slice_defined`, as
+    /// an offset in bytes from the start of the slice.
+    #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+    pub struct UndefinedAt {
+        pub offset: usize,
+    }
+
+    impl Error for UndefinedAt { }
+
+    impl fmt::Display for UndefinedAt {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "undefined memory at offset {}", self.offset)
+        }
+    }
+
+    /// Check that every byte of `data` is defined, and report an
+    /// error if not.  Padding bytes inside `T` are usually
+    /// undefined and will be reported, so this is best used with
+    /// element types that have no padding.
+    #[inline(always)]
+    pub fn check_slice_defined<T>(data: &[T]) -> Result<(), UndefinedAt> {
+        let start = data.as_ptr() as *const ();
+        match unsafe { check_mem_is_defined(start, mem::size_of_val(data)) } {
+            None => Ok(()),
+            Some(p) => Err(UndefinedAt { offset: p as usize - start as usize }),
+        }
+    }
+
+    #[inline(always)]
+    pub fn check_str_defined(s: &str) -> Result<(), UndefinedAt> {
+        check_slice_defined(s.as_bytes())
+    }
+
+    /// Mark `data` as undefined, for example after recycling a
+    /// buffer whose old contents must not be read.
+    #[inline(always)]
+    pub fn make_slice_undefined<T>(data: &mut [MaybeUninit<T>]) {
+        unsafe {
+            make_mem_undefined(data.as_ptr() as *const (), mem::size_of_val(data));
+        }
+    }
+
+    /// Mark `data` as defined.  Only sound if it really has been
+    /// initialized, for example by a system call Valgrind does not
+    /// understand.
+    #[inline(always)]
+    pub unsafe fn make_slice_defined<T>(data: &mut [MaybeUninit<T>]) {
+        make_mem_defined(data.as_ptr() as *const (), mem::size_of_val(data));
+    }
+
+    /// Mark the spare capacity of `v`, beyond its length, as
+    /// undefined.
+    #[inline(always)]
+    pub fn make_spare_capacity_undefined<T>(v: &mut Vec<T>) {
+        let size = mem::size_of::<T>();
+        unsafe {
+            make_mem_undefined(v.as_ptr().offset(v.len() as isize) as *const (),
+                (v.capacity() - v.len()) * size);
+        }
+    }
+
+    /// A description attached to a range of memory by
+    /// `describe_block`.  Memcheck forgets the description when
+    /// this handle is dropped.
+    #[must_use]
+    pub struct BlockHandle {
+        index: usize,
+    }
+
+    impl BlockHandle {
+        /// The block index assigned by Memcheck.
+        #[inline(always)]
+        pub fn index(&self) -> usize {
+            self.index
+        }
+    }
+
+    impl Drop for BlockHandle {
+        fn drop(&mut self) {
+            unsafe {
+                arch::request(0, enums::VG_USERREQ__DISCARD as usize, 0, self.index, 0, 0, 0);
+            }
+        }
+    }
+
+    /// Make Memcheck error messages refer to addresses in
+    /// `[addr, addr+len)` as being inside "a block described
+    /// as `desc`".
+    #[inline(always)]
+    pub unsafe fn describe_block(addr: *const (), len: usize, desc: &str) -> BlockHandle {
+        // Memcheck copies the description.
+        let c_str = CString::new(desc.as_bytes()).unwrap();
+        BlockHandle {
+            index: arch::request(0, enums::VG_USERREQ__CREATE_BLOCK as usize,
+                addr as usize, len, c_str.as_bytes_with_nul().as_ptr() as usize, 0, 0),
+        }
+    }
+
+    #[inline(always)]
+    pub unsafe fn describe<T>(obj: *const T, desc: &str) -> BlockHandle {
+        use std::mem::size_of;
+        describe_block(obj as *const (), size_of::<T>(), desc)
+    }
+
+    /// Validity bits for a range of memory, one byte of V-bits per
+    /// byte of data.  A set bit means the corresponding data bit
+    /// is undefined.
+    #[derive(Clone, PartialEq, Eq, Debug)]
+    pub struct VBits {
+        bits: Vec<u8>,
+    }
+
+    impl VBits {
+        #[inline(always)]
+        pub fn from_bytes(bits: Vec<u8>) -> VBits {
+            VBits { bits: bits }
+        }
+
+        #[inline(always)]
+        pub fn as_bytes(&self) -> &[u8] {
+            &self.bits
+        }
+
+        #[inline(always)]
+        pub fn len(&self) -> usize {
+            self.bits.len()
+        }
+
+        #[inline(always)]
+        pub fn is_empty(&self) -> bool {
+            self.bits.is_empty()
+        }
+
+        /// The V-bits for data byte `i`.
+        #[inline(always)]
+        pub fn byte(&self, i: usize) -> u8 {
+            self.bits[i]
+        }
+
+        #[inline(always)]
+        pub fn is_byte_defined(&self, i: usize) -> bool {
+            self.bits[i] == 0
+        }
+
+        /// Is bit `bit` (0 is least significant) of data byte `i`
+        /// defined?
+        #[inline(always)]
+        pub fn is_bit_defined(&self, i: usize, bit: usize) -> bool {
+            assert!(bit < 8);
+            self.bits[i] & (1 << bit) == 0
+        }
+
+        /// Is every bit defined?
+        #[inline(always)]
+        pub fn is_defined(&self) -> bool {
+            self.bits.iter().all(|&b| b == 0)
+        }
+    }
+
+    impl fmt::Display for VBits {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            for (n, line) in self.bits.chunks(16).enumerate() {
+                if n > 0 {
+                    write!(f, "\n")?;
+                }
+                write!(f, "{:08x}:", n * 16)?;
+                for b in line.iter() {
+                    write!(f, " {:02x}", *b)?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    /// Failure of `get_vbits` or `set_vbits`.
+    #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+    pub enum VBitsError {
+        /// Not running under Memcheck.
+        NotRunning,
+        /// Part of the data or the V-bits is not addressable.
+        Unaddressable,
+        /// The data and V-bits are misaligned.  Only reported by
+        /// old versions of Memcheck.
+        Misaligned,
+        /// The data and V-bits have different lengths.
+        LengthMismatch,
+    }
+
+    impl Error for VBitsError { }
+
+    impl fmt::Display for VBitsError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(match *self {
+                VBitsError::NotRunning => "not running under Memcheck",
+                VBitsError::Unaddressable => "memory is not addressable",
+                VBitsError::Misaligned => "memory is misaligned",
+                VBitsError::LengthMismatch => "data and V-bits differ in length",
+            })
+        }
+    }
+
+    #[inline(always)]
+    fn vbits_result(code: usize) -> Result<(), VBitsError> {
+        match code {
+            1 => Ok(()),
+            2 => Err(VBitsError::Misaligned),
+            3 => Err(VBitsError::Unaddressable),
+            _ => Err(VBitsError::NotRunning),
+        }
+    }
+
+    /// Get the V-bits of `data`.
+    #[inline(always)]
+    pub unsafe fn get_vbits(data: &[u8]) -> Result<VBits, VBitsError> {
+        let mut bits = vec![0; data.len()];
+        vbits_result(arch::request(0, enums::VG_USERREQ__GET_VBITS as usize,
+            data.as_ptr() as usize, bits.as_mut_ptr() as usize, data.len(), 0, 0))?;
+        Ok(VBits::from_bytes(bits))
+    }
+
+    /// Set the V-bits of `data`.  Fails with `LengthMismatch` unless
+    /// `vbits` has the same length.
+    #[inline(always)]
+    pub unsafe fn set_vbits(data: &[u8], vbits: &VBits) -> Result<(), VBitsError> {
+        if data.len() != vbits.len() {
+            return Err(VBitsError::LengthMismatch);
+        }
+        vbits_result(arch::request(0, enums::VG_USERREQ__SET_VBITS as usize,
+            data.as_ptr() as usize, vbits.bits.as_ptr() as usize, data.len(), 0, 0))
+    }
+
+    wrap!(VG_USERREQ__DISABLE_ADDR_ERROR_REPORTING_IN_RANGE
+        => fn disable_addr_error_reporting_in_range(addr: *const (), len: usize) -> ());
+
+    wrap!(VG_USERREQ__ENABLE_ADDR_ERROR_REPORTING_IN_RANGE
+        => fn enable_addr_error_reporting_in_range(addr: *const (), len: usize) -> ());
+
+    /// Addressability errors in `[addr, addr+len)` are not
+    /// reported while this guard is alive.
+    #[must_use]
+    pub struct AddrErrorsDisabled {
+        addr: *const (),
+        len: usize,
+    }
+
+    impl AddrErrorsDisabled {
+        #[inline(always)]
+        pub unsafe fn new(addr: *const (), len: usize) -> AddrErrorsDisabled {
+            disable_addr_error_reporting_in_range(addr, len);
+            AddrErrorsDisabled {
+                addr: addr,
+                len: len,
+            }
+        }
+    }
+
+    impl Drop for AddrErrorsDisabled {
+        fn drop(&mut self) {
+            unsafe {
+                enable_addr_error_reporting_in_range(self.addr, self.len);
+            }
+        }
+    }
+
     macro_rules! wrap_leak_check ( ($nr:ident($a1:expr, $a2:expr) => fn $name:ident () -> ()) => (
         #[inline(always)]
         pub unsafe fn $name() {
@@ -313,7 +1051,9 @@ pub mod callgrind {
     //!
     //! [section 6.5]: http://valgrind.org/docs/manual/cl-manual.html#cl-manual.clientrequests
 
+    use std::cell::RefCell;
     use std::ffi::CString;
+    use std::marker::PhantomData;
     use super::{arch, enums};
 
     wrap!(VG_USERREQ__DUMP_STATS
@@ -333,6 +1073,128 @@ pub mod callgrind {
 
     wrap!(VG_USERREQ__STOP_INSTRUMENTATION
         => fn stop_instrumentation() -> ());
+
+    // Names of the regions open on this thread, innermost last.
+    thread_local!(static REGIONS: RefCell<Vec<String>> = RefCell::new(Vec::new()));
+
+    /// A named region of code to profile.
+    ///
+    /// Entering the outermost region zeroes the statistics and
+    /// toggles collection on.  Dropping a region dumps the
+    /// statistics collected in it under its name.
+    ///
+    /// Costs go to the innermost open region only: entering a
+    /// nested region first dumps what the enclosing one has
+    /// collected so far, under the enclosing region's name.
+    /// Regions may be dropped in any order; costs collected since
+    /// the last dump still go to the innermost one.
+    ///
+    /// Zeroing and dumping statistics affect all threads, so
+    /// regions open at the same time on different threads will
+    /// mix up each other's costs.
+    #[must_use]
+    pub struct Region {
+        name: String,
+        // The stack of open regions is per-thread.
+        _marker: PhantomData<*const ()>,
+    }
+
+    impl Region {
+        /// Open a region.  The results are wrong unless the program
+        /// is run with `--collect-atstart=no`.
+        #[inline(always)]
+        pub unsafe fn new(name: &str) -> Region {
+            REGIONS.with(|regions| {
+                let mut regions = regions.borrow_mut();
+                match regions.last() {
+                    None => {
+                        zero_stats();
+                        toggle_collect();
+                    }
+                    Some(outer) => dump_stats_at(outer),
+                }
+                regions.push(name.to_string());
+            });
+            Region {
+                name: name.to_string(),
+                _marker: PhantomData,
+            }
+        }
+    }
+
+    impl Drop for Region {
+        fn drop(&mut self) {
+            REGIONS.with(|regions| {
+                let mut regions = regions.borrow_mut();
+                unsafe {
+                    // Whatever was collected since the last dump
+                    // belongs to the innermost region, which is not
+                    // this one if regions are dropped out of order.
+                    dump_stats_at(regions.last().unwrap());
+                    let i = regions.iter().rposition(|r| *r == self.name).unwrap();
+                    regions.remove(i);
+                    if regions.is_empty() {
+                        toggle_collect();
+                    }
+                }
+            });
+        }
+    }
+}
+
+pub mod cachegrind {
+    //! Client requests for the Cachegrind profiler tool.
+    //!
+    //! These need Valgrind 3.22 or later.  Use them with
+    //! `--instr-at-start=no` to measure only part of a program.
+    //!
+    //! See `/usr/include/valgrind/cachegrind.h` and
+    //! [chapter 5][] of the Valgrind manual.
+    //!
+    //! [chapter 5]: http://valgrind.org/docs/manual/cg-manual.html
+
+    use std::sync::Mutex;
+
+    wrap!(VG_USERREQ__CG_START_INSTRUMENTATION
+        => fn start_instrumentation() -> ());
+
+    wrap!(VG_USERREQ__CG_STOP_INSTRUMENTATION
+        => fn stop_instrumentation() -> ());
+
+    // Instrumentation is process-wide, so the number of live guards
+    // is too.  The requests are sent with the lock held so that
+    // starting and stopping cannot interleave.
+    static INSTRUMENTED: Mutex<usize> = Mutex::new(0);
+
+    /// Instrumentation is on while this guard is alive.  Guards
+    /// may be nested, on any threads; instrumentation stops when
+    /// the last one is dropped.
+    #[must_use]
+    pub struct Instrumented {
+        _private: (),
+    }
+
+    impl Instrumented {
+        #[inline(always)]
+        pub unsafe fn new() -> Instrumented {
+            let mut n = INSTRUMENTED.lock().unwrap();
+            if *n == 0 {
+                start_instrumentation();
+            }
+            *n += 1;
+            Instrumented { _private: () }
+        }
+    }
+
+    impl Drop for Instrumented {
+        fn drop(&mut self) {
+            let mut n = INSTRUMENTED.lock().unwrap();
+            *n -= 1;
+            if *n == 0 {
+                unsafe { stop_instrumentation(); }
+            }
+        }
+    }
 }
 
 pub mod helgrind {
@@ -349,6 +1211,345 @@ pub mod helgrind {
 
     generic!(clean_memory
         => fn clean<T>(obj: *const T) -> ());
+
+    // Happens-before edges between threads, keyed on an arbitrary
+    // address `obj`.  Every `annotate_happens_after(obj)` is ordered
+    // after all earlier `annotate_happens_before(obj)` calls.
+
+    wrap!(_VG_USERREQ__HG_USERSO_SEND_PRE
+        => fn annotate_happens_before(obj: *const ()) -> ());
+
+    wrap!(_VG_USERREQ__HG_USERSO_RECV_POST
+        => fn annotate_happens_after(obj: *const ()) -> ());
+
+    wrap!(_VG_USERREQ__HG_USERSO_FORGET_ALL
+        => fn annotate_happens_before_forget_all(obj: *const ()) -> ());
+
+    wrap!(_VG_USERREQ__HG_ARANGE_MAKE_UNTRACKED
+        => fn disable_checking(addr: *const (), len: usize) -> ());
+
+    generic!(disable_checking
+        => fn disable_checking_for<T>(obj: *const T) -> ());
+
+    wrap!(_VG_USERREQ__HG_ARANGE_MAKE_TRACKED
+        => fn enable_checking(addr: *const (), len: usize) -> ());
+
+    generic!(enable_checking
+        => fn enable_checking_for<T>(obj: *const T) -> ());
+
+    /// Accesses to `[addr, addr+len)` are not checked while this
+    /// guard is alive.
+    #[must_use]
+    pub struct CheckingDisabled {
+        addr: *const (),
+        len: usize,
+    }
+
+    impl CheckingDisabled {
+        #[inline(always)]
+        pub unsafe fn new(addr: *const (), len: usize) -> CheckingDisabled {
+            disable_checking(addr, len);
+            CheckingDisabled {
+                addr: addr,
+                len: len,
+            }
+        }
+
+        #[inline(always)]
+        pub unsafe fn for_obj<T>(obj: *const T) -> CheckingDisabled {
+            use std::mem::size_of;
+            CheckingDisabled::new(obj as *const (), size_of::<T>())
+        }
+    }
+
+    impl Drop for CheckingDisabled {
+        fn drop(&mut self) {
+            unsafe {
+                enable_checking(self.addr, self.len);
+            }
+        }
+    }
+
+    wrap!(_VG_USERREQ__HG_PTHREAD_BARRIER_INIT_PRE
+        => fn annotate_barrier_init(bar: *const (), count: usize, resizable: bool) -> ());
+
+    wrap!(_VG_USERREQ__HG_PTHREAD_BARRIER_RESIZE_PRE
+        => fn annotate_barrier_resize(bar: *const (), new_count: usize) -> ());
+
+    wrap!(_VG_USERREQ__HG_PTHREAD_BARRIER_WAIT_PRE
+        => fn annotate_barrier_wait_before(bar: *const ()) -> ());
+
+    /// Helgrind orders every thread after all the others as soon as
+    /// the last one calls `annotate_barrier_wait_before`, so there is
+    /// nothing to do here.  `helgrind.h` reports this annotation as
+    /// unimplemented; we stay silent instead.
+    #[inline(always)]
+    pub unsafe fn annotate_barrier_wait_after(_bar: *const ()) { }
+
+    wrap!(_VG_USERREQ__HG_PTHREAD_BARRIER_DESTROY_PRE
+        => fn annotate_barrier_destroy(bar: *const ()) -> ());
+
+    /// Annotations for a barrier which Helgrind does not intercept,
+    /// identified by its address.  One handle is shared by every
+    /// thread waiting on the barrier, and destroys it when dropped.
+    pub struct Barrier {
+        bar: *const (),
+    }
+
+    unsafe impl Send for Barrier { }
+    unsafe impl Sync for Barrier { }
+
+    impl Barrier {
+        /// `count` threads wait on the barrier in each phase.  Only
+        /// a `resizable` barrier may be passed to `resize`.
+        #[inline(always)]
+        pub unsafe fn new(bar: *const (), count: usize, resizable: bool) -> Barrier {
+            annotate_barrier_init(bar, count, resizable);
+            Barrier { bar: bar }
+        }
+
+        /// Change the number of threads waiting in each phase.
+        #[inline(always)]
+        pub unsafe fn resize(&self, new_count: usize) {
+            annotate_barrier_resize(self.bar, new_count)
+        }
+
+        /// Call before waiting on the barrier.
+        #[inline(always)]
+        pub unsafe fn wait_before(&self) {
+            annotate_barrier_wait_before(self.bar)
+        }
+
+        /// Call after waiting on the barrier.
+        #[inline(always)]
+        pub unsafe fn wait_after(&self) {
+            annotate_barrier_wait_after(self.bar)
+        }
+    }
+
+    impl Drop for Barrier {
+        fn drop(&mut self) {
+            unsafe {
+                annotate_barrier_destroy(self.bar);
+            }
+        }
+    }
+
+    pub mod sync {
+        //! Hooks for mutexes, condition variables and semaphores
+        //! which Helgrind does not intercept, such as those built
+        //! directly on futexes.
+        //!
+        //! Each primitive is identified by its address, which must
+        //! not change during its lifetime.  The hooks mirror the
+        //! calls Helgrind makes around the pthread functions.
+        //!
+        //! Waiting on a condition variable releases and reacquires
+        //! the mutex, and Helgrind must be told about both.  Call
+        //! `cond_wait_pre` then `mutex_unlock_pre` before blocking,
+        //! and `mutex_unlock_post`, `mutex_lock_post` then
+        //! `cond_wait_post` after waking.
+
+        use super::super::{arch, enums};
+
+        #[inline(always)]
+        unsafe fn req(nr: enums::Vg_TCheckClientRequest, a1: *const (), a2: usize, a3: usize) {
+            arch::request(0, nr as usize, a1 as usize, a2, a3, 0, 0);
+        }
+
+        /// The mutex has been initialized.
+        #[inline(always)]
+        pub unsafe fn mutex_init(mutex: *const (), recursive: bool) {
+            req(enums::_VG_USERREQ__HG_PTHREAD_MUTEX_INIT_POST, mutex, recursive as usize, 0)
+        }
+
+        /// The mutex is about to be destroyed.
+        #[inline(always)]
+        pub unsafe fn mutex_destroy(mutex: *const ()) {
+            req(enums::_VG_USERREQ__HG_PTHREAD_MUTEX_DESTROY_PRE, mutex, 0, 0)
+        }
+
+        /// The calling thread is about to lock the mutex.
+        #[inline(always)]
+        pub unsafe fn mutex_lock_pre(mutex: *const (), is_try_lock: bool) {
+            req(enums::_VG_USERREQ__HG_PTHREAD_MUTEX_ACQUIRE_PRE, mutex, is_try_lock as usize, 0)
+        }
+
+        /// The calling thread has locked the mutex.  Not to be
+        /// called if a try-lock failed.
+        #[inline(always)]
+        pub unsafe fn mutex_lock_post(mutex: *const ()) {
+            req(enums::_VG_USERREQ__HG_PTHREAD_MUTEX_ACQUIRE_POST, mutex, 0, 0)
+        }
+
+        /// The calling thread is about to unlock the mutex.
+        #[inline(always)]
+        pub unsafe fn mutex_unlock_pre(mutex: *const ()) {
+            req(enums::_VG_USERREQ__HG_PTHREAD_MUTEX_UNLOCK_PRE, mutex, 0, 0)
+        }
+
+        /// The calling thread has unlocked the mutex.
+        #[inline(always)]
+        pub unsafe fn mutex_unlock_post(mutex: *const ()) {
+            req(enums::_VG_USERREQ__HG_PTHREAD_MUTEX_UNLOCK_POST, mutex, 0, 0)
+        }
+
+        /// The condition variable has been initialized.
+        #[inline(always)]
+        pub unsafe fn cond_init(cond: *const ()) {
+            req(enums::_VG_USERREQ__HG_PTHREAD_COND_INIT_POST, cond, 0, 0)
+        }
+
+        /// The condition variable is about to be destroyed.
+        #[inline(always)]
+        pub unsafe fn cond_destroy(cond: *const ()) {
+            req(enums::_VG_USERREQ__HG_PTHREAD_COND_DESTROY_PRE, cond, 0, 0)
+        }
+
+        /// The calling thread is about to wake one waiter.
+        #[inline(always)]
+        pub unsafe fn cond_signal(cond: *const ()) {
+            req(enums::_VG_USERREQ__HG_PTHREAD_COND_SIGNAL_PRE, cond, 0, 0)
+        }
+
+        /// The calling thread is about to wake all waiters.
+        #[inline(always)]
+        pub unsafe fn cond_broadcast(cond: *const ()) {
+            req(enums::_VG_USERREQ__HG_PTHREAD_COND_BROADCAST_PRE, cond, 0, 0)
+        }
+
+        /// The calling thread, holding `mutex`, is about to wait on
+        /// the condition variable.  Follow with `mutex_unlock_pre`.
+        #[inline(always)]
+        pub unsafe fn cond_wait_pre(cond: *const (), mutex: *const ()) {
+            req(enums::_VG_USERREQ__HG_PTHREAD_COND_WAIT_PRE, cond, mutex as usize, 0)
+        }
+
+        /// The calling thread has finished waiting on the condition
+        /// variable and holds `mutex` again.  Call after
+        /// `mutex_unlock_post` and `mutex_lock_post`.
+        #[inline(always)]
+        pub unsafe fn cond_wait_post(cond: *const (), mutex: *const (), timed_out: bool) {
+            req(enums::_VG_USERREQ__HG_PTHREAD_COND_WAIT_POST, cond, mutex as usize,
+                timed_out as usize)
+        }
+
+        /// The semaphore has been initialized with `value`.
+        #[inline(always)]
+        pub unsafe fn sem_init(sem: *const (), value: usize) {
+            req(enums::_VG_USERREQ__HG_POSIX_SEM_INIT_POST, sem, value, 0)
+        }
+
+        /// The semaphore is about to be destroyed.
+        #[inline(always)]
+        pub unsafe fn sem_destroy(sem: *const ()) {
+            req(enums::_VG_USERREQ__HG_POSIX_SEM_DESTROY_PRE, sem, 0, 0)
+        }
+
+        /// The calling thread is about to post the semaphore.
+        #[inline(always)]
+        pub unsafe fn sem_post(sem: *const ()) {
+            req(enums::_VG_USERREQ__HG_POSIX_SEM_RELEASED, sem, 0, 0)
+        }
+
+        /// The calling thread has finished waiting on the semaphore.
+        /// Not to be called if the wait failed.
+        #[inline(always)]
+        pub unsafe fn sem_wait_post(sem: *const ()) {
+            req(enums::_VG_USERREQ__HG_POSIX_SEM_ACQUIRED, sem, 0, 0)
+        }
+    }
+
+    pub mod rwlock {
+        //! Annotations for reader-writer locks which Helgrind does
+        //! not intercept.
+        //!
+        //! These requests are binary compatible with DRD's
+        //! `ANNOTATE_RWLOCK_*`, so DRD understands them too.
+
+        use super::super::{arch, enums};
+
+        /// Which side of a reader-writer lock is held.
+        #[derive(Copy, Clone, PartialEq, Eq, Debug)]
+        pub enum Mode {
+            Reader,
+            Writer,
+        }
+
+        impl Mode {
+            #[inline(always)]
+            fn is_writer(self) -> usize {
+                match self {
+                    Mode::Reader => 0,
+                    Mode::Writer => 1,
+                }
+            }
+        }
+
+        /// The lock at `lock` has been created.
+        #[inline(always)]
+        pub unsafe fn create(lock: *const ()) {
+            arch::request(0, enums::_VG_USERREQ__HG_PTHREAD_RWLOCK_INIT_POST as usize,
+                lock as usize, 0, 0, 0, 0);
+        }
+
+        /// The lock at `lock` is about to be destroyed.
+        #[inline(always)]
+        pub unsafe fn destroy(lock: *const ()) {
+            arch::request(0, enums::_VG_USERREQ__HG_PTHREAD_RWLOCK_DESTROY_PRE as usize,
+                lock as usize, 0, 0, 0, 0);
+        }
+
+        /// The lock at `lock` has just been acquired.
+        #[inline(always)]
+        pub unsafe fn acquired(lock: *const (), mode: Mode) {
+            arch::request(0, enums::_VG_USERREQ__HG_PTHREAD_RWLOCK_ACQUIRED as usize,
+                lock as usize, mode.is_writer(), 0, 0, 0);
+        }
+
+        /// The lock at `lock` is about to be released.
+        #[inline(always)]
+        pub unsafe fn released(lock: *const (), mode: Mode) {
+            // Helgrind ignores the mode, but DRD uses it.
+            arch::request(0, enums::_VG_USERREQ__HG_PTHREAD_RWLOCK_RELEASED as usize,
+                lock as usize, mode.is_writer(), 0, 0, 0);
+        }
+
+        /// Annotation hooks for a lock type.  The implementation
+        /// should call `annotate_create` once constructed,
+        /// `annotate_acquired` after taking the lock,
+        /// `annotate_released` before releasing it, and
+        /// `annotate_destroy` before it goes away.
+        pub trait AnnotatedRwLock {
+            /// The address identifying this lock.  Defaults to the
+            /// address of `self`, so the lock must not move between
+            /// `annotate_create` and `annotate_destroy`.
+            #[inline(always)]
+            fn lock_addr(&self) -> *const () {
+                self as *const Self as *const ()
+            }
+
+            #[inline(always)]
+            unsafe fn annotate_create(&self) {
+                create(self.lock_addr())
+            }
+
+            #[inline(always)]
+            unsafe fn annotate_destroy(&self) {
+                destroy(self.lock_addr())
+            }
+
+            #[inline(always)]
+            unsafe fn annotate_acquired(&self, mode: Mode) {
+                acquired(self.lock_addr(), mode)
+            }
+
+            #[inline(always)]
+            unsafe fn annotate_released(&self, mode: Mode) {
+                released(self.lock_addr(), mode)
+            }
+        }
+    }
 }
 
 pub mod drd {
@@ -361,7 +1562,9 @@ pub mod drd {
     //! [section 8.2.5]: http://valgrind.org/docs/manual/drd-manual.html#drd-manual.clientreqs
 
     use libc::c_uint;
+    use std::cell::Cell;
     use std::ffi::CString;
+    use std::marker::PhantomData;
     use super::{arch, enums};
 
     wrap!(VG_USERREQ__DRD_CLEAN_MEMORY
@@ -370,6 +1573,32 @@ pub mod drd {
     generic!(clean_memory
         => fn clean<T>(obj: *const T) -> ());
 
+    // Memory handed out by a custom allocator.  DRD forgets all
+    // earlier accesses to it, as it does for memory from `malloc`.
+
+    #[inline(always)]
+    pub unsafe fn annotate_new_memory(addr: *const (), len: usize) {
+        clean_memory(addr, len)
+    }
+
+    generic!(annotate_new_memory
+        => fn annotate_new<T>(obj: *const T) -> ());
+
+    /// `drd.h` defines this annotation as a no-op, so it does
+    /// nothing.
+    #[inline(always)]
+    pub unsafe fn annotate_publish_memory_range(_addr: *const (), _len: usize) { }
+
+    /// DRD has no request for this annotation, so it does nothing.
+    /// Provided so that code annotated for other race detectors
+    /// builds unchanged.
+    #[inline(always)]
+    pub unsafe fn ignore_sync_begin() { }
+
+    /// See `ignore_sync_begin`.
+    #[inline(always)]
+    pub unsafe fn ignore_sync_end() { }
+
     wrap!(VG_USERREQ__DRD_GET_VALGRIND_THREAD_ID
         => fn get_valgrind_threadid() -> c_uint);
 
@@ -420,6 +1649,136 @@ pub mod drd {
     wrap_record!(VG_USERREQ__DRD_RECORD_STORES(1)
         => fn ignore_writes_end() -> ());
 
+    // DRD's per-thread flags are booleans, so we count nested guards
+    // ourselves and only flip them at the outermost level.
+    thread_local!(static READS_IGNORED: Cell<usize> = Cell::new(0));
+    thread_local!(static WRITES_IGNORED: Cell<usize> = Cell::new(0));
+
+    /// Loads by the current thread are ignored while this guard
+    /// is alive, including while unwinding.  Guards may be nested,
+    /// but should not be mixed with `ignore_reads_begin` and
+    /// `ignore_reads_end`.
+    #[must_use]
+    pub struct IgnoreReads {
+        _marker: PhantomData<*const ()>,
+    }
+
+    impl IgnoreReads {
+        #[inline(always)]
+        pub unsafe fn new() -> IgnoreReads {
+            READS_IGNORED.with(|n| {
+                if n.get() == 0 {
+                    ignore_reads_begin();
+                }
+                n.set(n.get() + 1);
+            });
+            IgnoreReads { _marker: PhantomData }
+        }
+    }
+
+    impl Drop for IgnoreReads {
+        fn drop(&mut self) {
+            READS_IGNORED.with(|n| {
+                n.set(n.get() - 1);
+                if n.get() == 0 {
+                    unsafe { ignore_reads_end(); }
+                }
+            });
+        }
+    }
+
+    /// Like `IgnoreReads`, for stores.
+    #[must_use]
+    pub struct IgnoreWrites {
+        _marker: PhantomData<*const ()>,
+    }
+
+    impl IgnoreWrites {
+        #[inline(always)]
+        pub unsafe fn new() -> IgnoreWrites {
+            WRITES_IGNORED.with(|n| {
+                if n.get() == 0 {
+                    ignore_writes_begin();
+                }
+                n.set(n.get() + 1);
+            });
+            IgnoreWrites { _marker: PhantomData }
+        }
+    }
+
+    impl Drop for IgnoreWrites {
+        fn drop(&mut self) {
+            WRITES_IGNORED.with(|n| {
+                n.set(n.get() - 1);
+                if n.get() == 0 {
+                    unsafe { ignore_writes_end(); }
+                }
+            });
+        }
+    }
+
+    /// Run `f` with both loads and stores by the current thread
+    /// ignored.
+    #[inline(always)]
+    pub unsafe fn ignoring<F, R>(f: F) -> R
+        where F: FnOnce() -> R
+    {
+        let _reads = IgnoreReads::new();
+        let _writes = IgnoreWrites::new();
+        f()
+    }
+
     wrap_str!(VG_USERREQ__DRD_SET_THREAD_NAME
         => fn annotate_thread_name(name: &str) -> ());
+
+    wrap!(VG_USERREQ__DRD_ANNOTATE_HAPPENS_BEFORE
+        => fn annotate_happens_before(obj: *const ()) -> ());
+
+    wrap!(VG_USERREQ__DRD_ANNOTATE_HAPPENS_AFTER
+        => fn annotate_happens_after(obj: *const ()) -> ());
+
+    wrap!(VG_USERREQ__DRD_ANNOTATE_RWLOCK_CREATE
+        => fn annotate_rwlock_create(lock: *const ()) -> ());
+
+    wrap!(VG_USERREQ__DRD_ANNOTATE_RWLOCK_DESTROY
+        => fn annotate_rwlock_destroy(lock: *const ()) -> ());
+
+    wrap!(VG_USERREQ__DRD_ANNOTATE_RWLOCK_ACQUIRED
+        => fn annotate_rwlock_acquired(lock: *const (), is_writer: bool) -> ());
+
+    wrap!(VG_USERREQ__DRD_ANNOTATE_RWLOCK_RELEASED
+        => fn annotate_rwlock_released(lock: *const (), is_writer: bool) -> ());
+
+    wrap!(VG_USERREQ__DRD_ANNOTATE_BARRIER_INIT_PRE
+        => fn annotate_barrier_init(bar: *const (), count: usize, reinit_allowed: bool) -> ());
+
+    wrap!(VG_USERREQ__DRD_ANNOTATE_BARRIER_DESTROY
+        => fn annotate_barrier_destroy(bar: *const ()) -> ());
+
+    wrap!(VG_USERREQ__DRD_ANNOTATE_BARRIER_WAIT_PRE
+        => fn annotate_barrier_wait_before(bar: *const ()) -> ());
+
+    wrap!(VG_USERREQ__DRD_ANNOTATE_BARRIER_WAIT_POST
+        => fn annotate_barrier_wait_after(bar: *const ()) -> ());
 }
+
+pub mod dhat {
+    //! Client requests for the DHAT heap profiler tool.
+    //!
+    //! See `/usr/include/valgrind/dhat.h` and
+    //! [chapter 10][] of the Valgrind manual.
+    //!
+    //! [chapter 10]: http://valgrind.org/docs/manual/dh-manual.html
+
+    // Record an event of the given weight.  Only meaningful
+    // with `--mode=ad-hoc`.
+    wrap!(VG_USERREQ__DHAT_AD_HOC_EVENT
+        => fn ad_hoc_event(weight: usize) -> ());
+
+    // Collect an access histogram for the heap block containing
+    // `addr`, even if it is larger than DHAT's usual limit.
+    wrap!(VG_USERREQ__DHAT_HISTOGRAM_MEMORY
+        => fn histogram_memory(addr: *const ()) -> ());
+}
+
+pub mod sync;
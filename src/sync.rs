@@ -0,0 +1,553 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Drop-in replacements for the `std::sync` locks and `Arc` which
+//! describe themselves to Helgrind and DRD.
+//!
+//! The standard primitives are built on futexes, which the Valgrind
+//! thread checkers may not intercept, so they report races on every
+//! access the locks protect.  These wrappers annotate each lock,
+//! unlock and wait.
+//!
+//! Each primitive keeps its state in a `Box`, so the address the
+//! tools know it by does not change when the wrapper is moved.
+//!
+//! The overhead is not zero, even when not running under Valgrind:
+//! every primitive costs a heap allocation, every operation goes
+//! through an extra pointer, and each annotation costs a few
+//! instructions which do nothing.  Use these in builds meant for
+//! checking, and the `std::sync` types elsewhere.
+
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::{fmt, isize, mem, process, ptr, sync};
+use std::sync::{LockResult, PoisonError, TryLockError, TryLockResult, WaitTimeoutResult};
+use std::sync::atomic::{self, AtomicUsize, Ordering};
+use std::time::Duration;
+
+use {drd, helgrind};
+use helgrind::rwlock::Mode;
+
+// Helgrind learns about mutexes and condition variables through the
+// `helgrind::sync` hooks.  DRD does not understand those, so we also
+// add happens-before edges.  DRD's happens-before requests are binary
+// compatible with Helgrind's, so Helgrind sees these edges as well;
+// for the locks they are redundant.
+//
+// The `helgrind::rwlock` requests are understood by both tools.
+
+fn map_lock_result<T, U, F>(r: LockResult<T>, f: F) -> LockResult<U>
+    where F: FnOnce(T) -> U
+{
+    match r {
+        Ok(t) => Ok(f(t)),
+        Err(p) => Err(PoisonError::new(f(p.into_inner()))),
+    }
+}
+
+fn map_try_lock_result<T, U, F>(r: TryLockResult<T>, f: F) -> TryLockResult<U>
+    where F: FnOnce(T) -> U
+{
+    match r {
+        Ok(t) => Ok(f(t)),
+        Err(TryLockError::Poisoned(p))
+            => Err(TryLockError::Poisoned(PoisonError::new(f(p.into_inner())))),
+        Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+    }
+}
+
+/// An annotated `std::sync::Mutex`.
+pub struct Mutex<T> {
+    inner: Box<sync::Mutex<T>>,
+}
+
+/// The guard returned by `Mutex::lock`.
+pub struct MutexGuard<'a, T: 'a> {
+    lock: &'a Mutex<T>,
+    // Taken by `Condvar::wait`, and on drop.
+    inner: Option<sync::MutexGuard<'a, T>>,
+}
+
+impl<T> Mutex<T> {
+    pub fn new(t: T) -> Mutex<T> {
+        let m = Mutex {
+            inner: Box::new(sync::Mutex::new(t)),
+        };
+        unsafe {
+            helgrind::sync::mutex_init(m.addr(), false);
+        }
+        m
+    }
+
+    fn addr(&self) -> *const () {
+        &*self.inner as *const sync::Mutex<T> as *const ()
+    }
+
+    fn guard<'a>(&'a self, g: sync::MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        unsafe {
+            helgrind::sync::mutex_lock_post(self.addr());
+            drd::annotate_happens_after(self.addr());
+        }
+        MutexGuard {
+            lock: self,
+            inner: Some(g),
+        }
+    }
+
+    pub fn lock<'a>(&'a self) -> LockResult<MutexGuard<'a, T>> {
+        unsafe {
+            helgrind::sync::mutex_lock_pre(self.addr(), false);
+        }
+        let r = self.inner.lock();
+        map_lock_result(r, |g| self.guard(g))
+    }
+
+    pub fn try_lock<'a>(&'a self) -> TryLockResult<MutexGuard<'a, T>> {
+        unsafe {
+            helgrind::sync::mutex_lock_pre(self.addr(), true);
+        }
+        let r = self.inner.try_lock();
+        map_try_lock_result(r, |g| self.guard(g))
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.inner.is_poisoned()
+    }
+
+    pub fn into_inner(self) -> LockResult<T> {
+        unsafe {
+            helgrind::sync::mutex_destroy(self.addr());
+            // Skip our `Drop`, which would destroy it again.
+            let inner = ptr::read(&self.inner);
+            mem::forget(self);
+            (*inner).into_inner()
+        }
+    }
+
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        self.inner.get_mut()
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Mutex<T> {
+        Mutex::new(T::default())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // Go through our own `try_lock` so that the tools see the
+        // access to the data.
+        match self.try_lock() {
+            Ok(g) => write!(f, "Mutex {{ data: {:?} }}", &*g),
+            Err(TryLockError::Poisoned(p))
+                => write!(f, "Mutex {{ data: Poisoned({:?}) }}", &*p.into_inner()),
+            Err(TryLockError::WouldBlock) => write!(f, "Mutex {{ <locked> }}"),
+        }
+    }
+}
+
+impl<T> Drop for Mutex<T> {
+    fn drop(&mut self) {
+        unsafe {
+            helgrind::sync::mutex_destroy(self.addr());
+        }
+    }
+}
+
+impl<'a, T> Deref for MutexGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.inner.as_ref().unwrap()
+    }
+}
+
+impl<'a, T> DerefMut for MutexGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.inner.as_mut().unwrap()
+    }
+}
+
+impl<'a, T> Drop for MutexGuard<'a, T> {
+    fn drop(&mut self) {
+        if let Some(g) = self.inner.take() {
+            let addr = self.lock.addr();
+            unsafe {
+                drd::annotate_happens_before(addr);
+                helgrind::sync::mutex_unlock_pre(addr);
+            }
+            drop(g);
+            unsafe {
+                helgrind::sync::mutex_unlock_post(addr);
+            }
+        }
+    }
+}
+
+/// An annotated `std::sync::RwLock`.
+pub struct RwLock<T> {
+    inner: Box<sync::RwLock<T>>,
+}
+
+/// The guard returned by `RwLock::read`.
+pub struct RwLockReadGuard<'a, T: 'a> {
+    lock: &'a RwLock<T>,
+    inner: sync::RwLockReadGuard<'a, T>,
+}
+
+/// The guard returned by `RwLock::write`.
+pub struct RwLockWriteGuard<'a, T: 'a> {
+    lock: &'a RwLock<T>,
+    inner: sync::RwLockWriteGuard<'a, T>,
+}
+
+impl<T> RwLock<T> {
+    pub fn new(t: T) -> RwLock<T> {
+        let l = RwLock {
+            inner: Box::new(sync::RwLock::new(t)),
+        };
+        unsafe {
+            helgrind::rwlock::create(l.addr());
+        }
+        l
+    }
+
+    fn addr(&self) -> *const () {
+        &*self.inner as *const sync::RwLock<T> as *const ()
+    }
+
+    fn read_guard<'a>(&'a self, g: sync::RwLockReadGuard<'a, T>) -> RwLockReadGuard<'a, T> {
+        unsafe {
+            helgrind::rwlock::acquired(self.addr(), Mode::Reader);
+        }
+        RwLockReadGuard {
+            lock: self,
+            inner: g,
+        }
+    }
+
+    fn write_guard<'a>(&'a self, g: sync::RwLockWriteGuard<'a, T>) -> RwLockWriteGuard<'a, T> {
+        unsafe {
+            helgrind::rwlock::acquired(self.addr(), Mode::Writer);
+        }
+        RwLockWriteGuard {
+            lock: self,
+            inner: g,
+        }
+    }
+
+    pub fn read<'a>(&'a self) -> LockResult<RwLockReadGuard<'a, T>> {
+        let r = self.inner.read();
+        map_lock_result(r, |g| self.read_guard(g))
+    }
+
+    pub fn try_read<'a>(&'a self) -> TryLockResult<RwLockReadGuard<'a, T>> {
+        let r = self.inner.try_read();
+        map_try_lock_result(r, |g| self.read_guard(g))
+    }
+
+    pub fn write<'a>(&'a self) -> LockResult<RwLockWriteGuard<'a, T>> {
+        let r = self.inner.write();
+        map_lock_result(r, |g| self.write_guard(g))
+    }
+
+    pub fn try_write<'a>(&'a self) -> TryLockResult<RwLockWriteGuard<'a, T>> {
+        let r = self.inner.try_write();
+        map_try_lock_result(r, |g| self.write_guard(g))
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.inner.is_poisoned()
+    }
+
+    pub fn into_inner(self) -> LockResult<T> {
+        unsafe {
+            helgrind::rwlock::destroy(self.addr());
+            // Skip our `Drop`, which would destroy it again.
+            let inner = ptr::read(&self.inner);
+            mem::forget(self);
+            (*inner).into_inner()
+        }
+    }
+
+    pub fn get_mut(&mut self) -> LockResult<&mut T> {
+        self.inner.get_mut()
+    }
+}
+
+impl<T: Default> Default for RwLock<T> {
+    fn default() -> RwLock<T> {
+        RwLock::new(T::default())
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for RwLock<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.try_read() {
+            Ok(g) => write!(f, "RwLock {{ data: {:?} }}", &*g),
+            Err(TryLockError::Poisoned(p))
+                => write!(f, "RwLock {{ data: Poisoned({:?}) }}", &*p.into_inner()),
+            Err(TryLockError::WouldBlock) => write!(f, "RwLock {{ <locked> }}"),
+        }
+    }
+}
+
+impl<T> Drop for RwLock<T> {
+    fn drop(&mut self) {
+        unsafe {
+            helgrind::rwlock::destroy(self.addr());
+        }
+    }
+}
+
+impl<'a, T> Deref for RwLockReadGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+// The annotation comes first; the lock itself is released
+// afterwards, when the `inner` field is dropped.
+
+impl<'a, T> Drop for RwLockReadGuard<'a, T> {
+    fn drop(&mut self) {
+        unsafe {
+            helgrind::rwlock::released(self.lock.addr(), Mode::Reader);
+        }
+    }
+}
+
+impl<'a, T> Deref for RwLockWriteGuard<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner
+    }
+}
+
+impl<'a, T> DerefMut for RwLockWriteGuard<'a, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+}
+
+impl<'a, T> Drop for RwLockWriteGuard<'a, T> {
+    fn drop(&mut self) {
+        unsafe {
+            helgrind::rwlock::released(self.lock.addr(), Mode::Writer);
+        }
+    }
+}
+
+/// An annotated `std::sync::Condvar`, for use with this module's
+/// `Mutex`.
+pub struct Condvar {
+    inner: Box<sync::Condvar>,
+}
+
+impl Condvar {
+    pub fn new() -> Condvar {
+        let c = Condvar {
+            inner: Box::new(sync::Condvar::new()),
+        };
+        unsafe {
+            helgrind::sync::cond_init(c.addr());
+        }
+        c
+    }
+
+    fn addr(&self) -> *const () {
+        &*self.inner as *const sync::Condvar as *const ()
+    }
+
+    // Waiting releases and reacquires the mutex; tell Helgrind in
+    // the same order as its pthread_cond_wait wrapper.
+    unsafe fn wait_pre(&self, mutex: *const ()) {
+        drd::annotate_happens_before(mutex);
+        helgrind::sync::cond_wait_pre(self.addr(), mutex);
+        helgrind::sync::mutex_unlock_pre(mutex);
+    }
+
+    unsafe fn wait_post(&self, mutex: *const (), timed_out: bool) {
+        helgrind::sync::mutex_unlock_post(mutex);
+        helgrind::sync::mutex_lock_post(mutex);
+        helgrind::sync::cond_wait_post(self.addr(), mutex, timed_out);
+        drd::annotate_happens_after(mutex);
+        drd::annotate_happens_after(self.addr());
+    }
+
+    pub fn wait<'a, T>(&self, mut guard: MutexGuard<'a, T>) -> LockResult<MutexGuard<'a, T>> {
+        let lock = guard.lock;
+        let g = guard.inner.take().unwrap();
+        unsafe {
+            self.wait_pre(lock.addr());
+        }
+        let r = self.inner.wait(g);
+        unsafe {
+            self.wait_post(lock.addr(), false);
+        }
+        map_lock_result(r, |g| MutexGuard {
+            lock: lock,
+            inner: Some(g),
+        })
+    }
+
+    pub fn wait_timeout<'a, T>(&self, mut guard: MutexGuard<'a, T>, dur: Duration)
+        -> LockResult<(MutexGuard<'a, T>, WaitTimeoutResult)>
+    {
+        let lock = guard.lock;
+        let g = guard.inner.take().unwrap();
+        unsafe {
+            self.wait_pre(lock.addr());
+        }
+        let r = self.inner.wait_timeout(g, dur);
+        let timed_out = match r {
+            Ok((_, t)) => t.timed_out(),
+            Err(ref p) => p.get_ref().1.timed_out(),
+        };
+        unsafe {
+            self.wait_post(lock.addr(), timed_out);
+        }
+        map_lock_result(r, |(g, t)| (MutexGuard {
+            lock: lock,
+            inner: Some(g),
+        }, t))
+    }
+
+    pub fn notify_one(&self) {
+        unsafe {
+            drd::annotate_happens_before(self.addr());
+            helgrind::sync::cond_signal(self.addr());
+        }
+        self.inner.notify_one()
+    }
+
+    pub fn notify_all(&self) {
+        unsafe {
+            drd::annotate_happens_before(self.addr());
+            helgrind::sync::cond_broadcast(self.addr());
+        }
+        self.inner.notify_all()
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Condvar {
+        Condvar::new()
+    }
+}
+
+impl Drop for Condvar {
+    fn drop(&mut self) {
+        unsafe {
+            helgrind::sync::cond_destroy(self.addr());
+        }
+    }
+}
+
+/// A thread-safe reference-counted pointer, like `std::sync::Arc`.
+///
+/// `std::sync::Arc` orders the final drop after all the others with
+/// an acquire fence, which the thread checkers cannot see, so they
+/// report races between a drop in one thread and the deallocation
+/// in another.  This `Arc` annotates its reference count instead.
+pub struct Arc<T> {
+    ptr: *mut ArcInner<T>,
+    _marker: PhantomData<ArcInner<T>>,
+}
+
+struct ArcInner<T> {
+    strong: AtomicUsize,
+    data: T,
+}
+
+unsafe impl<T: Send + Sync> Send for Arc<T> { }
+unsafe impl<T: Send + Sync> Sync for Arc<T> { }
+
+impl<T> Arc<T> {
+    pub fn new(data: T) -> Arc<T> {
+        let inner = Box::new(ArcInner {
+            strong: AtomicUsize::new(1),
+            data: data,
+        });
+        Arc {
+            ptr: Box::into_raw(inner),
+            _marker: PhantomData,
+        }
+    }
+
+    fn inner(&self) -> &ArcInner<T> {
+        unsafe { &*self.ptr }
+    }
+
+    // The reference count is the synchronization object.
+    fn addr(&self) -> *const () {
+        &self.inner().strong as *const AtomicUsize as *const ()
+    }
+
+    pub fn strong_count(this: &Arc<T>) -> usize {
+        this.inner().strong.load(Ordering::SeqCst)
+    }
+
+    /// A mutable reference to the contents, if this is the only
+    /// `Arc` pointing to them.
+    pub fn get_mut(this: &mut Arc<T>) -> Option<&mut T> {
+        if this.inner().strong.load(Ordering::Acquire) != 1 {
+            return None;
+        }
+        unsafe {
+            drd::annotate_happens_after(this.addr());
+            Some(&mut (*this.ptr).data)
+        }
+    }
+
+    pub fn ptr_eq(this: &Arc<T>, other: &Arc<T>) -> bool {
+        this.ptr == other.ptr
+    }
+}
+
+impl<T> Clone for Arc<T> {
+    fn clone(&self) -> Arc<T> {
+        let old = self.inner().strong.fetch_add(1, Ordering::Relaxed);
+        if old > isize::MAX as usize {
+            process::abort();
+        }
+        Arc {
+            ptr: self.ptr,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> Deref for Arc<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.inner().data
+    }
+}
+
+impl<T> Drop for Arc<T> {
+    fn drop(&mut self) {
+        let addr = self.addr();
+        unsafe {
+            drd::annotate_happens_before(addr);
+        }
+        if self.inner().strong.fetch_sub(1, Ordering::Release) != 1 {
+            return;
+        }
+        atomic::fence(Ordering::Acquire);
+        unsafe {
+            drd::annotate_happens_after(addr);
+            // Don't let a later object at the same address inherit
+            // this one's ordering.  DRD has no such request.
+            helgrind::annotate_happens_before_forget_all(addr);
+            drop(Box::from_raw(self.ptr));
+        }
+    }
+}
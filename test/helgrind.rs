@@ -0,0 +1,181 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+#![crate_name="vgrs_helgrind_test"]
+#![crate_type="bin"]
+#![deny(warnings)]
+
+extern crate vgrs;
+
+use vgrs::{valgrind, helgrind};
+use vgrs::sync::{Arc, Condvar, Mutex, RwLock};
+
+use std::thread;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+// Helgrind does not understand atomics, so the flags used below
+// to hand data between threads are made untracked.
+static READY: AtomicBool = AtomicBool::new(false);
+static ARRIVED: AtomicUsize = AtomicUsize::new(0);
+static LOCKED: AtomicBool = AtomicBool::new(false);
+
+// One thread waits on a condition variable for another
+fn condvar() {
+    let pair = Arc::new((Mutex::new(false), Condvar::new()));
+    let pair2 = pair.clone();
+    let t = thread::spawn(move || {
+        let (ref m, ref c) = *pair2;
+        *m.lock().unwrap() = true;
+        c.notify_one();
+    });
+
+    {
+        let (ref m, ref c) = *pair;
+        let mut ready = m.lock().unwrap();
+        while !*ready {
+            ready = c.wait(ready).unwrap();
+        }
+    }
+    t.join().unwrap();
+}
+
+// A writer and a reader on different threads
+fn rwlock() {
+    let lock = Arc::new(RwLock::new(0));
+    let lock2 = lock.clone();
+    let t = thread::spawn(move || {
+        *lock2.write().unwrap() += 1;
+    });
+
+    assert!(*lock.read().unwrap() <= 1);
+    t.join().unwrap();
+    assert_eq!(*lock.read().unwrap(), 1);
+}
+
+// A write and a read ordered by a flag and a happens-before edge
+unsafe fn happens_before() {
+    helgrind::disable_checking_for(&READY);
+    let data = Box::into_raw(Box::new(0usize)) as usize;
+    let t = thread::spawn(move || unsafe {
+        *(data as *mut usize) = 42;
+        helgrind::annotate_happens_before(&READY as *const AtomicBool as *const ());
+        READY.store(true, Ordering::Release);
+    });
+
+    while !READY.load(Ordering::Acquire) {
+        thread::yield_now();
+    }
+    helgrind::annotate_happens_after(&READY as *const AtomicBool as *const ());
+    assert_eq!(*(data as *const usize), 42);
+
+    t.join().unwrap();
+    drop(Box::from_raw(data as *mut usize));
+}
+
+// Unsynchronized increments are not reported while unchecked
+unsafe fn checking_disabled() {
+    let counter = Box::into_raw(Box::new(0usize)) as usize;
+    {
+        let _unchecked = helgrind::CheckingDisabled::for_obj(counter as *const usize);
+        let t = thread::spawn(move || unsafe {
+            *(counter as *mut usize) += 1;
+        });
+        *(counter as *mut usize) += 1;
+        t.join().unwrap();
+    }
+    drop(Box::from_raw(counter as *mut usize));
+}
+
+unsafe fn barrier_wait(bar: &helgrind::Barrier) {
+    bar.wait_before();
+    ARRIVED.fetch_add(1, Ordering::AcqRel);
+    while ARRIVED.load(Ordering::Acquire) < 2 {
+        thread::yield_now();
+    }
+    bar.wait_after();
+}
+
+// A write before a barrier is ordered before a read after it
+unsafe fn barrier() {
+    helgrind::disable_checking_for(&ARRIVED);
+    let bar = Arc::new(helgrind::Barrier::new(&ARRIVED as *const AtomicUsize as *const (),
+        2, false));
+    let data = Box::into_raw(Box::new(0usize)) as usize;
+    let bar2 = bar.clone();
+    let t = thread::spawn(move || unsafe {
+        *(data as *mut usize) = 42;
+        barrier_wait(&bar2);
+    });
+
+    barrier_wait(&bar);
+    assert_eq!(*(data as *const usize), 42);
+
+    t.join().unwrap();
+    drop(Box::from_raw(data as *mut usize));
+}
+
+fn lock_addr() -> *const () {
+    &LOCKED as *const AtomicBool as *const ()
+}
+
+unsafe fn spin_lock() {
+    helgrind::sync::mutex_lock_pre(lock_addr(), false);
+    while LOCKED.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+        thread::yield_now();
+    }
+    helgrind::sync::mutex_lock_post(lock_addr());
+}
+
+unsafe fn spin_unlock() {
+    helgrind::sync::mutex_unlock_pre(lock_addr());
+    LOCKED.store(false, Ordering::Release);
+    helgrind::sync::mutex_unlock_post(lock_addr());
+}
+
+// A counter protected by a spinlock which Helgrind only knows
+// about through the hooks
+unsafe fn spinlock() {
+    helgrind::disable_checking_for(&LOCKED);
+    helgrind::sync::mutex_init(lock_addr(), false);
+    let counter = Box::into_raw(Box::new(0usize)) as usize;
+    let t = thread::spawn(move || unsafe {
+        spin_lock();
+        *(counter as *mut usize) += 1;
+        spin_unlock();
+    });
+
+    spin_lock();
+    *(counter as *mut usize) += 1;
+    spin_unlock();
+
+    t.join().unwrap();
+    assert_eq!(*(counter as *const usize), 2);
+    helgrind::sync::mutex_destroy(lock_addr());
+    drop(Box::from_raw(counter as *mut usize));
+}
+
+fn main() {
+    unsafe {
+        assert_eq!(valgrind::running_on_valgrind(), 1);
+        assert_eq!(valgrind::count_errors(), 0);
+
+        condvar();
+        assert_eq!(valgrind::count_errors(), 0);
+
+        rwlock();
+        assert_eq!(valgrind::count_errors(), 0);
+
+        happens_before();
+        assert_eq!(valgrind::count_errors(), 0);
+
+        checking_disabled();
+        assert_eq!(valgrind::count_errors(), 0);
+
+        barrier();
+        assert_eq!(valgrind::count_errors(), 0);
+
+        spinlock();
+        assert_eq!(valgrind::count_errors(), 0);
+    }
+}
@@ -15,6 +15,7 @@ extern crate libc;
 use vgrs::{valgrind, memcheck};
 
 use std::mem;
+use std::mem::MaybeUninit;
 use libc::c_void;
 use test::black_box;
 
@@ -74,6 +75,87 @@ unsafe fn do_test() {
     black_box(x);
     assert_no_error(errors);
 
+    // Errors are not reported while disabled, even when nested
+    {
+        let _outer = valgrind::ErrorsDisabled::new();
+        let _inner = valgrind::ErrorsDisabled::new();
+        let x: u8 = mem::uninitialized();
+        black_box(x);
+    }
+    assert_no_error(errors);
+    let x: u8 = mem::uninitialized();
+    black_box(x);
+    assert_error(&mut errors);
+
+    // Address errors can be suppressed for a range
+    let x: u8 = 0;
+    memcheck::make_noaccess(&x);
+    {
+        let _guard = memcheck::AddrErrorsDisabled::new(&x as *const u8 as *const (), 1);
+        black_box(x);
+        assert_no_error(errors);
+    }
+    black_box(x);
+    assert_error(&mut errors);
+    memcheck::make_defined(&x);
+
+    // V-bits can be read and written directly
+    let buf = [0u8; 4];
+    memcheck::make_mem_undefined(buf.as_ptr().offset(1) as *const (), 1);
+    let vbits = memcheck::get_vbits(&buf).unwrap();
+    assert!(vbits.is_byte_defined(0));
+    assert!(!vbits.is_byte_defined(1));
+    assert_eq!(vbits.byte(1), 0xff);
+    assert!(!vbits.is_defined());
+    memcheck::set_vbits(&buf, &memcheck::VBits::from_bytes(vec![0; 4])).unwrap();
+    assert!(memcheck::check_mem_is_defined(buf.as_ptr() as *const (), 4).is_none());
+    assert_eq!(memcheck::set_vbits(&buf, &memcheck::VBits::from_bytes(vec![0; 3])),
+        Err(memcheck::VBitsError::LengthMismatch));
+    memcheck::make_mem_noaccess(buf.as_ptr() as *const (), 4);
+    assert_eq!(memcheck::get_vbits(&buf).err(), Some(memcheck::VBitsError::Unaddressable));
+    memcheck::make_mem_defined(buf.as_ptr() as *const (), 4);
+    assert_no_error(errors);
+
+    // Described blocks get distinct indices and are discarded on drop
+    let buf = [0u8; 16];
+    {
+        let first = memcheck::describe_block(buf.as_ptr() as *const (), 8, "first half");
+        let second = memcheck::describe_block(buf.as_ptr().offset(8) as *const (), 8,
+            "second half");
+        assert!(second.index() != 0);
+        assert!(second.index() != first.index());
+    }
+    assert_no_error(errors);
+
+    // Slice checks report offsets relative to the slice
+    let mut buf: [MaybeUninit<u8>; 8] = [MaybeUninit::new(0); 8];
+    assert!(memcheck::check_slice_defined(&buf).is_ok());
+    memcheck::make_slice_undefined(&mut buf[5..]);
+    assert_eq!(memcheck::check_slice_defined(&buf), Err(memcheck::UndefinedAt { offset: 5 }));
+    assert_error(&mut errors);
+    memcheck::make_slice_defined(&mut buf[5..]);
+    assert!(memcheck::check_slice_defined(&buf).is_ok());
+    assert!(memcheck::check_str_defined("vgrs").is_ok());
+    assert_no_error(errors);
+
+    // Mempool chunks are only accessible while allocated
+    let arena = libc::malloc(64) as *mut u8;
+    memcheck::make_mem_noaccess(arena as *const (), 64);
+    {
+        let pool = memcheck::Mempool::new(arena as *const (), 0, false);
+        assert!(pool.exists());
+        let chunk = arena.offset(16);
+        pool.alloc(chunk as *const (), 8);
+        *chunk = 1;
+        black_box(*chunk);
+        assert_no_error(errors);
+        pool.free(chunk as *const ());
+        black_box(*chunk);
+        assert_error(&mut errors);
+    }
+    assert!(!memcheck::mempool_exists(arena as *const ()));
+    libc::free(arena as *mut c_void);
+
     let mut x: *mut c_void = libc::malloc(42);
     assert_eq!(memcheck::count_leaks().leaked, 0);
     memcheck::do_leak_check();
@@ -6,13 +6,84 @@
 #![crate_type="bin"]
 #![deny(warnings)]
 
+#[macro_use]
 extern crate vgrs;
 
 use vgrs::valgrind;
+use vgrs::sync::{Arc, Condvar, Mutex, RwLock};
+
+use std::time::Duration;
+
+extern "C" fn add(_tid: usize, a: usize, b: usize) -> usize {
+    a + b
+}
 
 fn main() {
     unsafe {
         assert_eq!(valgrind::running_on_valgrind(), 1);
         assert_eq!(valgrind::count_errors(), 0);
+
+        assert!(vg_println!("Hello from {}, 100% Rust", "vgrs") > 0);
+        vg_println_backtrace!("Backtrace:");
+
+        assert!(valgrind::monitor_command("v.info n_errs_found").is_ok());
+        assert!(valgrind::monitor_command("no.such.command").is_err());
+
+        valgrind::change_option("--vgdb-error=999999");
+        valgrind::change_dyn_option(valgrind::DynOption::TimeStamp(false));
+
+        // Valgrind registers the main stack itself, so ours is not 0
+        let stack = vec![0u8; 4096];
+        {
+            let base = stack.as_ptr() as *const ();
+            let mut reg = valgrind::StackRegistration::new(base,
+                stack.as_ptr().offset(4096) as *const ());
+            assert!(reg.id() != 0);
+            reg.change(base, stack.as_ptr().offset(2048) as *const ());
+        }
+        assert_eq!(valgrind::count_errors(), 0);
+
+        let loc = valgrind::map_ip_to_srcloc(add as *const ()).unwrap();
+        assert!(loc.file().ends_with("valgrind.rs"));
+        assert!(loc.line() > 0);
+
+        assert_eq!(valgrind::non_simd_call2(add, 2, 3), 5);
+        let x = 42;
+        assert_eq!(valgrind::non_simd_call(|tid| tid + x), 1 + x);
+
+        let m = Mutex::new(1);
+        *m.lock().unwrap() += 1;
+        let c = Condvar::new();
+        let (g, t) = c.wait_timeout(m.lock().unwrap(), Duration::from_millis(1)).unwrap();
+        assert!(t.timed_out());
+        assert_eq!(*g, 2);
+        drop(g);
+        assert!(m.try_lock().is_ok());
+
+        let l = RwLock::new(1);
+        *l.write().unwrap() += 1;
+        assert_eq!(*l.read().unwrap(), 2);
+        {
+            let r = l.try_read().unwrap();
+            assert!(l.try_write().is_err());
+            assert_eq!(*r, 2);
+        }
+        *l.try_write().unwrap() += 1;
+        assert_eq!(format!("{:?}", l), "RwLock { data: 3 }");
+        assert_eq!(l.into_inner().unwrap(), 3);
+
+        let m: Mutex<u32> = Default::default();
+        assert_eq!(format!("{:?}", m), "Mutex { data: 0 }");
+        assert_eq!(m.into_inner().unwrap(), 0);
+        assert_eq!(*RwLock::<u32>::default().read().unwrap(), 0);
+
+        let mut a = Arc::new(1);
+        *Arc::get_mut(&mut a).unwrap() += 1;
+        let b = a.clone();
+        assert_eq!(Arc::strong_count(&a), 2);
+        assert!(Arc::get_mut(&mut a).is_none());
+        drop(b);
+        assert_eq!(*a, 2);
+        assert_eq!(valgrind::count_errors(), 0);
     }
 }